@@ -1,5 +1,10 @@
 use serde::{Deserialize, Serialize};
-use std::{hash::Hash, hint::unreachable_unchecked, ops::AddAssign};
+use std::{
+	collections::hash_map::DefaultHasher,
+	hash::{Hash, Hasher},
+	hint::unreachable_unchecked,
+	ops::AddAssign,
+};
 
 // We opt to store strings as String even at the overhead of needing to convert
 // back nad forth to Vec<char> for multibyte unicode support because it reduces
@@ -8,9 +13,10 @@ use std::{hash::Hash, hint::unreachable_unchecked, ops::AddAssign};
 
 /// Node of the post body tree
 //
-// TODO: bump allocation for entire tree to reduce allocation/deallocation
-// overhead. Depends on https://github.com/rust-lang/rust/issues/32838
-#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+// Bump/pool allocation for entire tree lives in `arena::NodeArena`, which
+// converts to and from this type at the serialization boundary - see its
+// doc comment for why.
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq, Hash)]
 pub enum Node {
 	/// No content
 	Empty,
@@ -80,6 +86,126 @@ impl Default for Node {
 	}
 }
 
+/// Above this many children on either side, `Node::diff` falls back to the
+/// cheap positional truncate/append patch instead of running the O(n*m) LCS
+/// DP, to bound worst-case diff cost for pathologically long lists.
+const CHILDREN_DIFF_LCS_LIMIT: usize = 512;
+
+/// The old behaviour for diffing `Children` lists: walk both lists
+/// positionally and, on the first length divergence, truncate the tail and
+/// append the new one. Used as a fallback above `CHILDREN_DIFF_LCS_LIMIT`.
+fn positional_children_patch(old: &[Node], new: &[Node]) -> Option<Patch> {
+	let mut patch = vec![];
+	let mut truncate = None;
+	let mut append = vec![];
+
+	let mut old_it = old.iter();
+	let mut new_it = new.iter();
+	let mut i = 0;
+	loop {
+		match (old_it.next(), new_it.next()) {
+			(Some(o), Some(n)) => {
+				if let Some(p) = o.diff(n) {
+					patch.push((i, p));
+				}
+			}
+			(None, Some(n)) => {
+				append.push(n.clone());
+				append.extend(new_it.map(Clone::clone));
+				break;
+			}
+			(Some(_), None) => {
+				truncate = Some(i);
+				break;
+			}
+			(None, None) => break,
+		};
+		i += 1;
+	}
+
+	if patch.is_empty() && truncate.is_none() && append.is_empty() {
+		None
+	} else {
+		Some(Patch::Children {
+			patch,
+			truncate,
+			append,
+		})
+	}
+}
+
+/// Match weight between two candidate children for the LCS below. `None`
+/// means the nodes can never be paired (different shape). Same-shape nodes
+/// can always be paired (so a `Keep` can carry a recursive sub-patch when
+/// their contents differ), but an exact `PartialEq` match scores higher, so
+/// the DP prefers aligning truly-unchanged nodes over merely-same-shape
+/// ones when it has a choice - e.g. reordering two same-variant nodes is
+/// cheaper to express as delete+insert than as keep+patch.
+#[inline]
+fn child_match_weight(a: &Node, b: &Node) -> Option<u32> {
+	if std::mem::discriminant(a) != std::mem::discriminant(b) {
+		None
+	} else if a == b {
+		// Must outweigh two same-shape pairings, so the reorder above
+		// does not tie with keep+patch.
+		Some(3)
+	} else {
+		Some(1)
+	}
+}
+
+/// Build a minimal edit script turning `old` into `new` via the weighted
+/// LCS of the two child lists (see `child_match_weight`).
+fn children_edit_script(old: &[Node], new: &[Node]) -> Vec<ChildOp> {
+	let n = old.len();
+	let m = new.len();
+
+	let mut dp = vec![vec![0u32; m + 1]; n + 1];
+	for i in (0..n).rev() {
+		for j in (0..m).rev() {
+			let matched = child_match_weight(&old[i], &new[j])
+				.map_or(0, |w| dp[i + 1][j + 1] + w);
+			dp[i][j] = matched.max(dp[i + 1][j]).max(dp[i][j + 1]);
+		}
+	}
+
+	let mut ops = Vec::new();
+	let (mut i, mut j) = (0usize, 0usize);
+	while i < n && j < m {
+		if let Some(w) = child_match_weight(&old[i], &new[j]) {
+			if dp[i][j] == dp[i + 1][j + 1] + w {
+				ops.push(ChildOp::Keep {
+					old_idx: i,
+					patch: old[i].diff(&new[j]).map(Box::new),
+				});
+				i += 1;
+				j += 1;
+				continue;
+			}
+		}
+		if dp[i + 1][j] >= dp[i][j + 1] {
+			ops.push(ChildOp::Delete { old_idx: i });
+			i += 1;
+		} else {
+			ops.push(ChildOp::Insert {
+				node: new[j].clone(),
+			});
+			j += 1;
+		}
+	}
+	while i < n {
+		ops.push(ChildOp::Delete { old_idx: i });
+		i += 1;
+	}
+	while j < m {
+		ops.push(ChildOp::Insert {
+			node: new[j].clone(),
+		});
+		j += 1;
+	}
+	ops
+}
+
 impl Node {
 	/// Construct a new text node
 	#[inline]
@@ -106,42 +232,19 @@ impl Node {
 		match (self, new) {
 			(Empty, Empty) | (NewLine, NewLine) => None,
 			(Children(old), Children(new)) => {
-				let mut patch = vec![];
-				let mut truncate = None;
-				let mut append = vec![];
-
-				let mut old_it = old.iter();
-				let mut new_it = new.iter();
-				let mut i = 0;
-				loop {
-					match (old_it.next(), new_it.next()) {
-						(Some(o), Some(n)) => {
-							if let Some(p) = o.diff(n) {
-								patch.push((i, p));
-							}
-						}
-						(None, Some(n)) => {
-							append.push(n.clone());
-							append.extend(new_it.map(Clone::clone));
-							break;
-						}
-						(Some(_), None) => {
-							truncate = Some(i);
-							break;
-						}
-						(None, None) => break,
-					};
-					i += 1;
+				if old == new {
+					return None;
 				}
-
-				if patch.is_empty() && truncate.is_none() && append.is_empty() {
-					None
-				} else {
-					Some(Patch::Children {
-						patch,
-						truncate,
-						append,
+				// Bound the O(n*m) DP cost for pathologically long lists and
+				// fall back to the cheap positional patch instead.
+				if old.len() <= CHILDREN_DIFF_LCS_LIMIT
+					&& new.len() <= CHILDREN_DIFF_LCS_LIMIT
+				{
+					Some(Patch::ChildrenEdit {
+						ops: children_edit_script(old, new),
 					})
+				} else {
+					positional_children_patch(old, new)
 				}
 			}
 			(Children(old), new @ _) if old.len() == 1 => old[0].diff(new),
@@ -203,6 +306,36 @@ impl Node {
 				}
 				dst.extend(append);
 			}
+			(Node::Children(dst), Patch::ChildrenEdit { ops }) => {
+				let mut old: Vec<Option<Node>> =
+					std::mem::take(dst).into_iter().map(Some).collect();
+				let mut result = Vec::with_capacity(ops.len());
+				for op in ops {
+					match op {
+						ChildOp::Keep { old_idx, patch } => {
+							let l = old.len();
+							let mut node = old
+								.get_mut(old_idx)
+								.and_then(Option::take)
+								.ok_or_else(|| {
+									format!("patch out of bounds: {} >= {}", old_idx, l)
+								})?;
+							if let Some(p) = patch {
+								node.patch(*p)?;
+							}
+							result.push(node);
+						}
+						ChildOp::Delete { old_idx } => {
+							let l = old.len();
+							old.get_mut(old_idx).and_then(Option::take).ok_or_else(
+								|| format!("patch out of bounds: {} >= {}", old_idx, l),
+							)?;
+						}
+						ChildOp::Insert { node } => result.push(node),
+					}
+				}
+				*dst = result;
+			}
 
 			// Real ugly shit because you can't bind both dst and the contents
 			// of Node::Children at the same time
@@ -219,7 +352,8 @@ impl Node {
 				dst.patch(p)?;
 			}
 
-			(dst @ _, p @ Patch::Children { .. }) => {
+			(dst @ _, p @ Patch::Children { .. })
+			| (dst @ _, p @ Patch::ChildrenEdit { .. }) => {
 				*dst = Node::Children(vec![std::mem::take(dst)]);
 				dst.patch(p)?;
 			}
@@ -245,6 +379,171 @@ impl Node {
 			}
 		})
 	}
+
+	/// Compute this subtree's structural content hash, reusing `cache`
+	/// where it's still valid and filling in whatever was missing or
+	/// invalidated. Folds each child's hash into the parent, so the
+	/// returned hash fully summarizes the subtree (Merkle-style) and two
+	/// subtrees with equal hashes are structurally identical with
+	/// overwhelming probability.
+	pub fn content_hash(&self, cache: &mut HashCache) -> u64 {
+		if let Some(h) = cache.hash {
+			return h;
+		}
+
+		let h = match self {
+			Node::Children(children) => {
+				if !matches!(cache.children, HashCacheChildren::Children(_)) {
+					cache.children = HashCacheChildren::Children(Vec::new());
+				}
+				let child_caches = match &mut cache.children {
+					HashCacheChildren::Children(c) => c,
+					_ => unsafe { unreachable_unchecked() },
+				};
+				child_caches.resize_with(children.len(), HashCache::empty);
+
+				let mut hasher = DefaultHasher::new();
+				std::mem::discriminant(self).hash(&mut hasher);
+				for (child, c) in children.iter().zip(child_caches.iter_mut()) {
+					child.content_hash(c).hash(&mut hasher);
+				}
+				hasher.finish()
+			}
+			Node::Spoiler(inner)
+			| Node::Bold(inner)
+			| Node::Italic(inner)
+			| Node::Quoted(inner) => {
+				if !matches!(cache.children, HashCacheChildren::Wrapped(_)) {
+					cache.children =
+						HashCacheChildren::Wrapped(Box::new(HashCache::empty()));
+				}
+				let inner_cache = match &mut cache.children {
+					HashCacheChildren::Wrapped(c) => c.as_mut(),
+					_ => unsafe { unreachable_unchecked() },
+				};
+				let mut hasher = DefaultHasher::new();
+				std::mem::discriminant(self).hash(&mut hasher);
+				inner.content_hash(inner_cache).hash(&mut hasher);
+				hasher.finish()
+			}
+			leaf => {
+				let mut hasher = DefaultHasher::new();
+				leaf.hash(&mut hasher);
+				hasher.finish()
+			}
+		};
+		cache.hash = Some(h);
+		h
+	}
+
+	/// Like `diff`, but first compares the memoized structural hashes of
+	/// the two roots. A hash match is only ever a hint, so it's confirmed
+	/// with a full `PartialEq` check before short-circuiting to `None`; on
+	/// a hash mismatch, or a same-hash `PartialEq` mismatch (a collision),
+	/// this falls back to the full `diff`, which is the authoritative
+	/// result. Only the roots' hashes are consulted: `diff` does not read
+	/// the caches, so it still descends into unchanged subtrees below a
+	/// changed root.
+	/// Callers that don't maintain a `HashCache` alongside their tree should
+	/// just call `diff` directly.
+	pub fn diff_hashed(
+		&self,
+		new: &Self,
+		old_cache: &mut HashCache,
+		new_cache: &mut HashCache,
+	) -> Option<Patch> {
+		if self.content_hash(old_cache) == new.content_hash(new_cache) && self == new {
+			return None;
+		}
+		self.diff(new)
+	}
+}
+
+/// Memoized structural content hash for a `Node`, alongside memoized hashes
+/// for its descendants, so a `Patch` only has to invalidate and recompute
+/// the path from the root to the node it actually touched. Lives alongside
+/// its `Node` rather than inside it, so `Node`'s wire format and equality
+/// semantics are unaffected - build one with `HashCache::empty()` and keep
+/// it next to the tree it caches.
+#[derive(Debug, Clone)]
+pub struct HashCache {
+	hash: Option<u64>,
+	children: HashCacheChildren,
+}
+
+#[derive(Debug, Clone)]
+enum HashCacheChildren {
+	None,
+	Children(Vec<HashCache>),
+	Wrapped(Box<HashCache>),
+}
+
+impl HashCache {
+	/// A fresh, fully invalidated cache.
+	pub fn empty() -> Self {
+		Self {
+			hash: None,
+			children: HashCacheChildren::None,
+		}
+	}
+
+	/// The memoized hash, if `content_hash` has computed it since the last
+	/// invalidation.
+	pub fn hash(&self) -> Option<u64> {
+		self.hash
+	}
+
+	/// Invalidate the cached hashes along the path a `Patch` is about to
+	/// touch. Call before applying the patch to the equivalent `Node`; the
+	/// next `content_hash` call then only recomputes the invalidated path
+	/// and reuses everything else.
+	pub fn invalidate(&mut self, patch: &Patch) {
+		self.hash = None;
+		match (&mut self.children, patch) {
+			(
+				HashCacheChildren::Children(children),
+				Patch::Children {
+					patch,
+					truncate,
+					append,
+				},
+			) => {
+				for (i, p) in patch {
+					if let Some(c) = children.get_mut(*i) {
+						c.invalidate(p);
+					}
+				}
+				if let Some(len) = truncate {
+					children.truncate(*len);
+				}
+				children.extend(append.iter().map(|_| HashCache::empty()));
+			}
+			(HashCacheChildren::Children(children), Patch::ChildrenEdit { ops }) => {
+				let mut next = Vec::with_capacity(ops.len());
+				for op in ops {
+					match op {
+						ChildOp::Keep { old_idx, patch } => {
+							let mut c = children
+								.get(*old_idx)
+								.cloned()
+								.unwrap_or_else(HashCache::empty);
+							if let Some(p) = patch {
+								c.invalidate(p);
+							}
+							next.push(c);
+						}
+						ChildOp::Delete { .. } => {}
+						ChildOp::Insert { .. } => next.push(HashCache::empty()),
+					}
+				}
+				*children = next;
+			}
+			(HashCacheChildren::Wrapped(inner), Patch::Wrapped(p)) => inner.invalidate(p),
+			// Replace / Text, or a shape mismatch between the cache and the
+			// patch: nothing finer-grained to reuse below this point.
+			_ => self.children = HashCacheChildren::None,
+		}
+	}
 }
 
 impl AddAssign<Node> for Node {
@@ -364,7 +663,7 @@ impl_ref_add_assign! {
 /// Node dependant on some database access or processing and pending
 /// finalization.
 /// Used by the server. These must never make it to the client.
-#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq, Hash)]
 #[serde(rename_all = "snake_case")]
 pub enum PendingNode {
 	Flip,
@@ -394,7 +693,7 @@ pub enum PendingNode {
 }
 
 /// Hash command result
-#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq, Hash)]
 #[serde(rename_all = "snake_case")]
 pub enum Command {
 	/// Describes the parameters and results of one dice throw
@@ -478,6 +777,34 @@ pub enum Patch {
 		/// Then append these nodes
 		append: Vec<Node>,
 	},
+
+	/// Apply a minimal edit script to children, as produced by the LCS
+	/// diff. Preferred over `Children` up to `CHILDREN_DIFF_LCS_LIMIT`
+	/// children, since it only touches the nodes that actually moved,
+	/// changed or got inserted/deleted instead of reserializing the tail
+	/// of the list after the first divergence.
+	ChildrenEdit { ops: Vec<ChildOp> },
+}
+
+/// Single operation in a `Patch::ChildrenEdit` script. Emitted in
+/// left-to-right order relative to the evolving destination list, so
+/// `Node::patch` can apply them directly by iterating the ops and building
+/// the new list as it goes - no index recomputation needed.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum ChildOp {
+	/// Keep the node at `old_idx`, optionally patching it in place if its
+	/// contents changed.
+	Keep {
+		old_idx: usize,
+		patch: Option<Box<Patch>>,
+	},
+
+	/// Drop the node at `old_idx`.
+	Delete { old_idx: usize },
+
+	/// Insert a new node at this point in the destination being built.
+	Insert { node: Node },
 }
 
 /// Patch to apply to the text body of a post
@@ -550,14 +877,717 @@ impl TextPatch {
 	}
 }
 
+/// Bump/pool allocator backing a post body [`Node`] tree.
+///
+/// `Node` trees are parsed, diffed, and held in memory for every post on
+/// both server and client, which makes their thousands of tiny `Vec`/`Box`
+/// allocations add up fast. `NodeArena` bump-allocates nodes out of a single
+/// growable `Vec` and recycles freed slots through an intrusive freelist
+/// instead of going through the global allocator one node at a time.
+/// `Children` lists live in a shared buffer and are recycled per size class
+/// (bucketed by capacity, rounded up to the next power of two), so editing a
+/// post and dropping its old tree returns blocks to the arena rather than to
+/// the global allocator. `reset` reclaims everything at once, e.g. when a
+/// connection closes.
+///
+/// This is purely an in-memory representation - the wire format is still
+/// the plain `Box`/`Vec`-based [`Node`], unchanged. Use
+/// [`NodeArena::from_node`] and [`NodeArena::to_node`] to convert at the
+/// serialization boundary.
+pub mod arena {
+	use super::*;
+
+	/// Handle to a node stored in a [`NodeArena`]. Only valid for the arena
+	/// that produced it.
+	#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+	pub struct NodeId(u32);
+
+	/// Span of child [`NodeId`]s inside a [`NodeArena`]'s shared children
+	/// buffer. `cap` may be larger than `len` - the slack is reused in
+	/// place as children get appended.
+	#[derive(Debug, Clone, Copy)]
+	struct ChildSpan {
+		start: u32,
+		len: u32,
+		cap: u32,
+	}
+
+	/// Mirror of [`Node`], with owning pointers (`Box<Node>`, `Vec<Node>`)
+	/// replaced by handles into the [`NodeArena`] that holds it.
+	#[derive(Debug, Clone)]
+	enum ArenaNode {
+		Empty,
+		NewLine,
+		Children(ChildSpan),
+		Text(String),
+		PostLink { id: u64, thread: u64, page: u32 },
+		Command(Command),
+		URL(String),
+		Reference { label: String, url: String },
+		Embed(Embed),
+		Code(String),
+		Spoiler(NodeId),
+		Bold(NodeId),
+		Italic(NodeId),
+		Quoted(NodeId),
+		Pending(PendingNode),
+	}
+
+	enum Slot {
+		Occupied(ArenaNode),
+		Free(Option<u32>),
+	}
+
+	/// Number of buckets used to recycle children spans by capacity size
+	/// class (1, 2, 4, ..., 2^(SIZE_CLASSES - 1)).
+	const SIZE_CLASSES: usize = 16;
+
+	#[inline]
+	fn size_class(cap: u32) -> usize {
+		(32 - cap.max(1).leading_zeros())
+			.saturating_sub(1)
+			.min(SIZE_CLASSES as u32 - 1) as usize
+	}
+
+	pub struct NodeArena {
+		nodes: Vec<Slot>,
+		node_free: Option<u32>,
+		children: Vec<NodeId>,
+		children_free: Vec<Vec<(u32, u32)>>,
+	}
+
+	impl NodeArena {
+		pub fn new() -> Self {
+			Self {
+				nodes: Vec::new(),
+				node_free: None,
+				children: Vec::new(),
+				children_free: vec![Vec::new(); SIZE_CLASSES],
+			}
+		}
+
+		fn get(&self, id: NodeId) -> &ArenaNode {
+			match &self.nodes[id.0 as usize] {
+				Slot::Occupied(n) => n,
+				Slot::Free(_) => panic!("NodeArena: use after free: {:?}", id),
+			}
+		}
+
+		fn get_mut(&mut self, id: NodeId) -> &mut ArenaNode {
+			match &mut self.nodes[id.0 as usize] {
+				Slot::Occupied(n) => n,
+				Slot::Free(_) => panic!("NodeArena: use after free: {:?}", id),
+			}
+		}
+
+		fn alloc(&mut self, node: ArenaNode) -> NodeId {
+			match self.node_free {
+				Some(i) => {
+					self.node_free = match &self.nodes[i as usize] {
+						Slot::Free(next) => *next,
+						Slot::Occupied(_) => unreachable!(),
+					};
+					self.nodes[i as usize] = Slot::Occupied(node);
+					NodeId(i)
+				}
+				None => {
+					self.nodes.push(Slot::Occupied(node));
+					NodeId(self.nodes.len() as u32 - 1)
+				}
+			}
+		}
+
+		fn alloc_children(&mut self, ids: &[NodeId]) -> ChildSpan {
+			let len = ids.len() as u32;
+			let cap = len.max(1).next_power_of_two();
+			let class = size_class(cap);
+			// Above SIZE_CLASSES, `size_class` saturates and buckets spans of
+			// different actual capacities together, so a blind pop could hand
+			// back a block smaller than `cap` and corrupt whatever follows it
+			// in `children`. Only recycle a block that's actually big enough;
+			// leave smaller ones in the bucket for a smaller future request.
+			let reusable = self.children_free[class]
+				.iter()
+				.position(|&(_, free_cap)| free_cap >= cap);
+			if let Some(i) = reusable {
+				let (start, free_cap) = self.children_free[class].swap_remove(i);
+				self.children[start as usize..start as usize + len as usize]
+					.copy_from_slice(ids);
+				return ChildSpan {
+					start,
+					len,
+					cap: free_cap,
+				};
+			}
+			let start = self.children.len() as u32;
+			self.children.extend_from_slice(ids);
+			self.children.resize(
+				self.children.len() + (cap - len) as usize,
+				// Never read past `len` - just keeps the spare capacity
+				// slots occupied until a push grows into them.
+				NodeId(u32::MAX),
+			);
+			ChildSpan { start, len, cap }
+		}
+
+		fn free_children(&mut self, span: ChildSpan) {
+			self.children_free[size_class(span.cap)]
+				.push((span.start, span.cap));
+		}
+
+		fn last_child(&self, dst: NodeId) -> Option<NodeId> {
+			match self.get(dst) {
+				ArenaNode::Children(span) if span.len > 0 => {
+					Some(self.children[(span.start + span.len - 1) as usize])
+				}
+				_ => None,
+			}
+		}
+
+		fn push_child(&mut self, dst: NodeId, child: NodeId) {
+			let span = match self.get(dst) {
+				ArenaNode::Children(span) => *span,
+				_ => unreachable!(),
+			};
+			if span.len < span.cap {
+				self.children[(span.start + span.len) as usize] = child;
+				if let ArenaNode::Children(span) = self.get_mut(dst) {
+					span.len += 1;
+				}
+				return;
+			}
+			let mut ids = self.children
+				[span.start as usize..(span.start + span.len) as usize]
+				.to_vec();
+			ids.push(child);
+			self.free_children(span);
+			let grown = self.alloc_children(&ids);
+			if let ArenaNode::Children(s) = self.get_mut(dst) {
+				*s = grown;
+			}
+		}
+
+		/// Recursively return a node and everything it owns to the arena's
+		/// freelists.
+		pub fn free(&mut self, id: NodeId) {
+			let node =
+				std::mem::replace(&mut self.nodes[id.0 as usize], Slot::Free(None));
+			if let Slot::Occupied(n) = node {
+				match n {
+					ArenaNode::Children(span) => {
+						for i in span.start..span.start + span.len {
+							let child = self.children[i as usize];
+							self.free(child);
+						}
+						self.free_children(span);
+					}
+					ArenaNode::Spoiler(c)
+					| ArenaNode::Bold(c)
+					| ArenaNode::Italic(c)
+					| ArenaNode::Quoted(c) => self.free(c),
+					_ => {}
+				}
+			}
+			self.nodes[id.0 as usize] = Slot::Free(self.node_free);
+			self.node_free = Some(id.0);
+		}
+
+		/// Reclaim all storage at once, e.g. when a connection closes.
+		/// Keeps the backing allocations around for reuse.
+		pub fn reset(&mut self) {
+			self.nodes.clear();
+			self.node_free = None;
+			self.children.clear();
+			for bucket in &mut self.children_free {
+				bucket.clear();
+			}
+		}
+
+		/// Copy an owned [`Node`] tree into the arena.
+		pub fn from_node(&mut self, node: &Node) -> NodeId {
+			let arena_node = match node {
+				Node::Empty => ArenaNode::Empty,
+				Node::NewLine => ArenaNode::NewLine,
+				Node::Children(children) => {
+					let ids: Vec<NodeId> =
+						children.iter().map(|c| self.from_node(c)).collect();
+					ArenaNode::Children(self.alloc_children(&ids))
+				}
+				Node::Text(s) => ArenaNode::Text(s.clone()),
+				Node::PostLink { id, thread, page } => ArenaNode::PostLink {
+					id: *id,
+					thread: *thread,
+					page: *page,
+				},
+				Node::Command(c) => ArenaNode::Command(c.clone()),
+				Node::URL(s) => ArenaNode::URL(s.clone()),
+				Node::Reference { label, url } => ArenaNode::Reference {
+					label: label.clone(),
+					url: url.clone(),
+				},
+				Node::Embed(e) => ArenaNode::Embed(e.clone()),
+				Node::Code(s) => ArenaNode::Code(s.clone()),
+				Node::Spoiler(n) => ArenaNode::Spoiler(self.from_node(n)),
+				Node::Bold(n) => ArenaNode::Bold(self.from_node(n)),
+				Node::Italic(n) => ArenaNode::Italic(self.from_node(n)),
+				Node::Quoted(n) => ArenaNode::Quoted(self.from_node(n)),
+				Node::Pending(p) => ArenaNode::Pending(p.clone()),
+			};
+			self.alloc(arena_node)
+		}
+
+		/// Convert an arena-stored subtree back into an owned [`Node`] for
+		/// serialization.
+		pub fn to_node(&self, id: NodeId) -> Node {
+			match self.get(id) {
+				ArenaNode::Empty => Node::Empty,
+				ArenaNode::NewLine => Node::NewLine,
+				ArenaNode::Children(span) => Node::Children(
+					self.children
+						[span.start as usize..(span.start + span.len) as usize]
+						.iter()
+						.map(|&c| self.to_node(c))
+						.collect(),
+				),
+				ArenaNode::Text(s) => Node::Text(s.clone()),
+				ArenaNode::PostLink { id, thread, page } => Node::PostLink {
+					id: *id,
+					thread: *thread,
+					page: *page,
+				},
+				ArenaNode::Command(c) => Node::Command(c.clone()),
+				ArenaNode::URL(s) => Node::URL(s.clone()),
+				ArenaNode::Reference { label, url } => Node::Reference {
+					label: label.clone(),
+					url: url.clone(),
+				},
+				ArenaNode::Embed(e) => Node::Embed(e.clone()),
+				ArenaNode::Code(s) => Node::Code(s.clone()),
+				ArenaNode::Spoiler(c) => Node::Spoiler(Box::new(self.to_node(*c))),
+				ArenaNode::Bold(c) => Node::Bold(Box::new(self.to_node(*c))),
+				ArenaNode::Italic(c) => Node::Italic(Box::new(self.to_node(*c))),
+				ArenaNode::Quoted(c) => Node::Quoted(Box::new(self.to_node(*c))),
+				ArenaNode::Pending(p) => Node::Pending(p.clone()),
+			}
+		}
+
+		/// Arena-aware equivalent of `Node += Node`. `src` is consumed: it
+		/// becomes a new child of `dst`, is merged into an adjacent text
+		/// node, or replaces an `Empty` `dst` - mirroring
+		/// `Node::add_assign` but appending into arena storage instead of
+		/// reallocating the destination. If both `dst` and `src` are
+		/// `Children` lists, `src`'s children are flattened into `dst` one
+		/// at a time (merging the leading one into `dst`'s last child same
+		/// as any other append) rather than nested as a single child.
+		pub fn append(&mut self, dst: NodeId, src: NodeId) {
+			if let ArenaNode::Empty = self.get(src) {
+				self.free(src);
+				return;
+			}
+			if let ArenaNode::Empty = self.get(dst) {
+				let moved = std::mem::replace(self.get_mut(src), ArenaNode::Empty);
+				*self.get_mut(dst) = moved;
+				self.free(src);
+				return;
+			}
+			if let (ArenaNode::Children(_), ArenaNode::Children(src_span)) =
+				(self.get(dst), self.get(src))
+			{
+				let src_span = *src_span;
+				let ids = self.children[src_span.start as usize
+					..(src_span.start + src_span.len) as usize]
+					.to_vec();
+				self.free_children(src_span);
+				self.nodes[src.0 as usize] = Slot::Free(self.node_free);
+				self.node_free = Some(src.0);
+
+				let mut rest = ids.into_iter();
+				if let Some(first) = rest.next() {
+					self.append(dst, first);
+				}
+				for id in rest {
+					self.push_child(dst, id);
+				}
+				return;
+			}
+			let merge_target = match self.get(dst) {
+				ArenaNode::Text(_) => Some(dst),
+				ArenaNode::Children(_) => self.last_child(dst),
+				_ => None,
+			};
+			match merge_target {
+				Some(target)
+					if matches!(self.get(target), ArenaNode::Text(_))
+						&& matches!(self.get(src), ArenaNode::Text(_)) =>
+				{
+					let s = match std::mem::replace(self.get_mut(src), ArenaNode::Empty)
+					{
+						ArenaNode::Text(s) => s,
+						_ => unreachable!(),
+					};
+					self.free(src);
+					if let ArenaNode::Text(d) = self.get_mut(target) {
+						d.push_str(&s);
+					}
+					return;
+				}
+				_ => {}
+			}
+			match self.get(dst) {
+				ArenaNode::Children(_) => self.push_child(dst, src),
+				_ => {
+					let old = std::mem::replace(self.get_mut(dst), ArenaNode::Empty);
+					let old_id = self.alloc(old);
+					let span = self.alloc_children(&[old_id, src]);
+					*self.get_mut(dst) = ArenaNode::Children(span);
+				}
+			}
+		}
+
+		/// Arena-aware equivalent of `Node += &str`/`Node += char`. Avoids
+		/// reallocating the destination by appending directly into
+		/// arena-owned storage.
+		pub fn append_str(&mut self, dst: NodeId, rhs: &str) {
+			if let ArenaNode::Text(s) = self.get_mut(dst) {
+				s.push_str(rhs);
+				return;
+			}
+			if let Some(ArenaNode::Text(s)) =
+				self.last_child(dst).map(|last| self.get_mut(last))
+			{
+				s.push_str(rhs);
+				return;
+			}
+			match self.get(dst) {
+				ArenaNode::Children(_) => {
+					let text = self.alloc(ArenaNode::Text(rhs.to_owned()));
+					self.push_child(dst, text);
+				}
+				ArenaNode::Empty => {
+					*self.get_mut(dst) = ArenaNode::Text(rhs.to_owned());
+				}
+				_ => {
+					let old = std::mem::replace(self.get_mut(dst), ArenaNode::Empty);
+					let old_id = self.alloc(old);
+					let text = self.alloc(ArenaNode::Text(rhs.to_owned()));
+					let span = self.alloc_children(&[old_id, text]);
+					*self.get_mut(dst) = ArenaNode::Children(span);
+				}
+			}
+		}
+
+		pub fn append_char(&mut self, dst: NodeId, rhs: char) {
+			let mut buf = [0u8; 4];
+			self.append_str(dst, rhs.encode_utf8(&mut buf));
+		}
+	}
+
+	impl Default for NodeArena {
+		#[inline]
+		fn default() -> Self {
+			Self::new()
+		}
+	}
+
+	#[cfg(test)]
+	mod test {
+		use super::*;
+
+		fn sample_tree() -> Node {
+			Node::Children(vec![
+				Node::text("hello "),
+				Node::Bold(Box::new(Node::text("world"))),
+				Node::Quoted(Box::new(Node::Children(vec![
+					Node::text(">nested"),
+					Node::Spoiler(Box::new(Node::text("secret"))),
+				]))),
+				Node::PostLink {
+					id: 1,
+					thread: 2,
+					page: 0,
+				},
+			])
+		}
+
+		#[test]
+		fn round_trips_through_the_arena() {
+			let tree = sample_tree();
+			let mut arena = NodeArena::new();
+			let id = arena.from_node(&tree);
+			assert_eq!(arena.to_node(id), tree);
+		}
+
+		#[test]
+		fn free_recycles_the_node_slot() {
+			let mut arena = NodeArena::new();
+			let a = arena.from_node(&Node::text("a"));
+			arena.free(a);
+			let b = arena.from_node(&Node::text("b"));
+			// The freelist is LIFO, so the just-freed slot is reused.
+			assert_eq!(a, b);
+			assert_eq!(arena.to_node(b), Node::text("b"));
+		}
+
+		#[test]
+		fn free_recursively_reclaims_children_and_wrapped_nodes() {
+			let mut arena = NodeArena::new();
+			let tree = arena.from_node(&sample_tree());
+			let (nodes, children) = (arena.nodes.len(), arena.children.len());
+			arena.free(tree);
+			// Every node the tree owned, including nested Bold/Quoted/
+			// Spoiler/Children, should be back on the freelist, so
+			// allocating the same shape again reuses the same ids.
+			let again = arena.from_node(&sample_tree());
+			assert_eq!(arena.to_node(again), sample_tree());
+			assert_eq!(arena.nodes.len(), nodes);
+			assert_eq!(arena.children.len(), children);
+		}
+
+		#[test]
+		fn reset_reclaims_everything_at_once() {
+			let mut arena = NodeArena::new();
+			let id = arena.from_node(&sample_tree());
+			assert_eq!(arena.to_node(id), sample_tree());
+
+			arena.reset();
+
+			let id = arena.from_node(&Node::text("fresh"));
+			assert_eq!(arena.to_node(id), Node::text("fresh"));
+			assert_eq!(arena.nodes.len(), 1);
+			assert!(arena.children.is_empty());
+		}
+
+		#[test]
+		fn append_merges_adjacent_text() {
+			let mut arena = NodeArena::new();
+			let dst = arena.from_node(&Node::text("foo"));
+			let src = arena.from_node(&Node::text("bar"));
+			arena.append(dst, src);
+			assert_eq!(arena.to_node(dst), Node::text("foobar"));
+		}
+
+		#[test]
+		fn append_promotes_to_children_and_keeps_growing() {
+			let mut arena = NodeArena::new();
+			let dst = arena.from_node(&Node::NewLine);
+			for s in ["a", "b", "c", "d", "e"] {
+				let src = arena.from_node(&Node::text(s));
+				arena.append(dst, src);
+			}
+			assert_eq!(
+				arena.to_node(dst),
+				Node::Children(vec![
+					Node::NewLine,
+					Node::text("abcde"),
+				])
+			);
+		}
+
+		#[test]
+		fn append_flattens_a_children_src_instead_of_nesting_it() {
+			// Mirrors Node::add_assign's (Children, Children) arm: the
+			// leading child merges into dst's last child if both are text,
+			// and the rest are appended as siblings rather than nested
+			// under one extra Children wrapper.
+			let mut arena = NodeArena::new();
+			let dst = arena.from_node(&Node::Children(vec![Node::text("a")]));
+			let src = arena.from_node(&Node::Children(vec![
+				Node::text("b"),
+				Node::text("c"),
+			]));
+			arena.append(dst, src);
+			assert_eq!(
+				arena.to_node(dst),
+				Node::Children(vec![Node::text("ab"), Node::text("c")])
+			);
+		}
+
+		#[test]
+		fn append_str_and_char_avoid_replacing_the_destination() {
+			let mut arena = NodeArena::new();
+			let dst = arena.from_node(&Node::text("ab"));
+			arena.append_str(dst, "cd");
+			arena.append_char(dst, 'e');
+			assert_eq!(arena.to_node(dst), Node::text("abcde"));
+		}
+
+		#[test]
+		fn children_spans_recycle_across_many_sizes() {
+			// Exercise alloc/free of children spans across several size
+			// classes so the per-class freelists get real traffic.
+			let mut arena = NodeArena::new();
+			for len in [1usize, 2, 3, 8, 9, 33, 1] {
+				let tree = Node::Children(
+					(0..len).map(|i| Node::text(i.to_string())).collect(),
+				);
+				let id = arena.from_node(&tree);
+				assert_eq!(arena.to_node(id), tree);
+				arena.free(id);
+			}
+		}
+	}
+}
+
 #[cfg(test)]
 mod test {
-	use super::TextPatch;
+	use super::*;
+
+	fn children(strs: &[&str]) -> Node {
+		Node::Children(strs.iter().map(|s| Node::text(*s)).collect())
+	}
+
+	// Diff `old` against `new`, apply the resulting patch (if any) to `old`
+	// and assert it reproduces `new`.
+	fn assert_round_trips(mut old: Node, new: Node) {
+		if let Some(patch) = old.diff(&new) {
+			old.patch(patch).expect("patch should apply cleanly");
+		}
+		assert_eq!(old, new);
+	}
+
+	#[test]
+	fn node_diff_children_insert_at_front() {
+		assert_round_trips(children(&["b", "c"]), children(&["a", "b", "c"]));
+	}
+
+	#[test]
+	fn node_diff_children_delete_at_front() {
+		assert_round_trips(children(&["a", "b", "c"]), children(&["b", "c"]));
+	}
 
-	// Test diffing and patching nodes
 	#[test]
-	fn node_diff() {
-		// TODO
+	fn node_diff_children_reorder() {
+		assert_round_trips(children(&["a", "b"]), children(&["b", "a"]));
+	}
+
+	#[test]
+	fn node_diff_children_edit_in_place() {
+		assert_round_trips(children(&["a", "b"]), children(&["a", "bb"]));
+	}
+
+	// Above CHILDREN_DIFF_LCS_LIMIT children, diff must fall back to the
+	// positional truncate/append patch instead of running the LCS DP, but
+	// still round-trip correctly.
+	#[test]
+	fn node_diff_children_lcs_fallback() {
+		let old: Vec<Node> = (0..CHILDREN_DIFF_LCS_LIMIT + 1)
+			.map(|i| Node::text(i.to_string()))
+			.collect();
+		let mut new = old.clone();
+		new.insert(0, Node::text("inserted"));
+
+		match Node::Children(old.clone()).diff(&Node::Children(new.clone())) {
+			Some(Patch::Children { .. }) => {}
+			other => panic!("expected positional fallback patch, got {:?}", other),
+		}
+
+		assert_round_trips(Node::Children(old), Node::Children(new));
+	}
+
+	fn sample_tree() -> Node {
+		Node::Children(vec![
+			Node::text("hello "),
+			Node::Bold(Box::new(Node::text("world"))),
+			Node::Quoted(Box::new(Node::Children(vec![
+				Node::text(">nested"),
+				Node::Spoiler(Box::new(Node::text("secret"))),
+			]))),
+		])
+	}
+
+	#[test]
+	fn content_hash_is_stable_and_structural() {
+		let mut cache_a = HashCache::empty();
+		let mut cache_b = HashCache::empty();
+		let a = sample_tree();
+		let b = sample_tree();
+
+		assert_eq!(a.content_hash(&mut cache_a), b.content_hash(&mut cache_b));
+		assert!(cache_a.hash().is_some());
+
+		let mut cache_c = HashCache::empty();
+		let different = children(&["hello ", "world"]);
+		assert_ne!(a.content_hash(&mut cache_a), different.content_hash(&mut cache_c));
+	}
+
+	#[test]
+	fn diff_hashed_short_circuits_on_equal_hashes() {
+		let a = sample_tree();
+		let b = sample_tree();
+		let mut cache_a = HashCache::empty();
+		let mut cache_b = HashCache::empty();
+
+		// Prime both caches first, the way a long-lived caller would.
+		a.content_hash(&mut cache_a);
+		b.content_hash(&mut cache_b);
+
+		assert!(a.diff_hashed(&b, &mut cache_a, &mut cache_b).is_none());
+	}
+
+	#[test]
+	fn diff_hashed_matches_diff_after_an_edit() {
+		let old = sample_tree();
+		let mut old_cache = HashCache::empty();
+		old.content_hash(&mut old_cache);
+
+		let new = Node::Children(vec![
+			Node::text("hello "),
+			Node::Bold(Box::new(Node::text("world!"))),
+			Node::Quoted(Box::new(Node::Children(vec![
+				Node::text(">nested"),
+				Node::Spoiler(Box::new(Node::text("secret"))),
+			]))),
+		]);
+		let mut new_cache = HashCache::empty();
+
+		let expected = old.diff(&new);
+		assert!(expected.is_some());
+		assert_eq!(
+			format!("{:?}", old.diff_hashed(&new, &mut old_cache, &mut new_cache)),
+			format!("{:?}", expected)
+		);
+	}
+
+	#[test]
+	fn invalidate_only_recomputes_the_patched_path() {
+		let mut tree = sample_tree();
+		let mut cache = HashCache::empty();
+		tree.content_hash(&mut cache);
+
+		let children = match &cache.children {
+			HashCacheChildren::Children(c) => c,
+			_ => panic!("expected a Children cache"),
+		};
+		// The Quoted subtree (index 2) wasn't touched, so its hash should
+		// still be cached going in.
+		assert!(children[2].hash().is_some());
+
+		let new = Node::Children(vec![
+			Node::text("hello "),
+			Node::Bold(Box::new(Node::text("world!"))),
+			Node::Quoted(Box::new(Node::Children(vec![
+				Node::text(">nested"),
+				Node::Spoiler(Box::new(Node::text("secret"))),
+			]))),
+		]);
+		let patch = tree.diff(&new).expect("trees differ");
+		cache.invalidate(&patch);
+
+		// Root hash is always invalidated...
+		assert_eq!(cache.hash(), None);
+		let children = match &cache.children {
+			HashCacheChildren::Children(c) => c,
+			_ => panic!("expected a Children cache"),
+		};
+		// ...but the untouched Quoted sibling's cached hash survives.
+		assert!(children[2].hash().is_some());
+
+		tree.patch(patch).expect("patch should apply cleanly");
+		let recomputed = tree.content_hash(&mut cache);
+
+		let mut fresh_cache = HashCache::empty();
+		let fresh = new.content_hash(&mut fresh_cache);
+		assert_eq!(recomputed, fresh);
 	}
 
 	// Test diffing and patching text